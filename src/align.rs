@@ -0,0 +1,32 @@
+//! Hardware depth→color registration.
+//!
+//! Depth and color are captured from independent sensors, so a raw depth
+//! pixel `(col, row)` does not land on the same physical point as the color
+//! pixel at the same coordinates. Running RealSense's align processing block
+//! on the frameset before we serialize it fixes that: afterwards both images
+//! share one image plane and one set of intrinsics, so a consumer can index
+//! both with a single `(u, v)` and colorize depth directly. This is the same
+//! registration step RGB-D bridges (e.g. the ROS RealSense wrapper) run
+//! before publishing.
+
+use anyhow::Result;
+use realsense_rust::{frame::CompositeFrame, kind::Rs2StreamKind, processing_blocks::Align};
+
+pub struct DepthColorAligner {
+    align: Align,
+}
+
+impl DepthColorAligner {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            align: Align::new(Rs2StreamKind::Color)?,
+        })
+    }
+
+    /// Run alignment on a frameset pulled from `pipeline.wait`, returning a
+    /// frameset whose depth frame is reprojected into the color stream's
+    /// image plane.
+    pub fn align(&mut self, frames: CompositeFrame) -> Result<CompositeFrame> {
+        Ok(self.align.process(frames)?)
+    }
+}
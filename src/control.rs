@@ -0,0 +1,203 @@
+//! Runtime control over streaming parameters.
+//!
+//! Resolution, FPS, JPEG quality/subsampling, zstd level, and which streams
+//! run at all used to be compile-time constants in `main`. `ControlState` is
+//! a small shared, lock-protected settings box that a `camera/control`
+//! zenoh queryable (wired up in `main`) writes into; the capture thread and
+//! the encode/publish workers read from it every iteration. This mirrors how
+//! rover/robot camera stacks let an operator drop quality or disable RGB on
+//! the fly to manage bandwidth, without restarting the process.
+
+use std::sync::Mutex;
+
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+use turbojpeg::Subsamp;
+
+/// Wire-friendly stand-in for `turbojpeg::Subsamp`, which isn't `Encode`/`Decode`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum JpegSubsampling {
+    Sub444,
+    Sub422,
+    Sub420,
+}
+
+impl JpegSubsampling {
+    pub fn to_turbojpeg(self) -> Subsamp {
+        match self {
+            JpegSubsampling::Sub444 => Subsamp::None,
+            JpegSubsampling::Sub422 => Subsamp::Sub2x1,
+            JpegSubsampling::Sub420 => Subsamp::Sub2x2,
+        }
+    }
+}
+
+/// zstd's documented compression-level bounds (see `ZSTD_minCLevel`/
+/// `ZSTD_maxCLevel`); `SetDepthCompressionLevel` clamps to this range.
+const MIN_DEPTH_ZSTD_LEVEL: i32 = 1;
+const MAX_DEPTH_ZSTD_LEVEL: i32 = 22;
+
+/// Sane bounds for `SetTargetFps`. The D435i only actually supports a
+/// handful of discrete rates per stream profile (e.g. 6/15/30/60/90), so
+/// clamping here can't guarantee the device accepts the value -- it just
+/// keeps an obviously-bogus operator input (0, or something absurd) from
+/// reaching `build_config`. The capture thread still has to handle a
+/// clamped-but-unsupported value failing at `pipeline.start`.
+const MIN_TARGET_FPS: u32 = 1;
+const MAX_TARGET_FPS: u32 = 90;
+
+/// A command accepted on the `camera/control` queryable.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Encode, Decode)]
+pub enum ControlCommand {
+    SetColorEnabled(bool),
+    SetJpegQuality {
+        quality: u8,
+        subsamp: JpegSubsampling,
+    },
+    SetTargetFps(u32),
+    SetDepthCompressionLevel(i32),
+}
+
+impl ControlCommand {
+    pub fn decode(buf: &[u8]) -> anyhow::Result<Self> {
+        let (cmd, _) = bincode::decode_from_slice(buf, bincode::config::standard())?;
+        Ok(cmd)
+    }
+}
+
+/// The current value of every runtime-tunable streaming parameter.
+#[derive(Debug, Clone, Copy)]
+pub struct StreamParams {
+    pub color_enabled: bool,
+    pub jpeg_quality: u8,
+    pub jpeg_subsamp: JpegSubsampling,
+    pub depth_zstd_level: i32,
+    pub target_fps: u32,
+}
+
+impl Default for StreamParams {
+    fn default() -> Self {
+        Self {
+            color_enabled: true,
+            jpeg_quality: 75,
+            jpeg_subsamp: JpegSubsampling::Sub420,
+            depth_zstd_level: 3,
+            target_fps: 30,
+        }
+    }
+}
+
+/// Shared stream parameters. Quality/subsampling/zstd-level changes take
+/// effect on the next frame a worker encodes; `color_enabled` and
+/// `target_fps` also set the pending-restart flag, since those require
+/// restarting the RealSense pipeline with a new `Config`.
+pub struct ControlState {
+    params: Mutex<StreamParams>,
+    restart_requested: Mutex<bool>,
+}
+
+impl ControlState {
+    pub fn new(initial: StreamParams) -> Self {
+        Self {
+            params: Mutex::new(initial),
+            restart_requested: Mutex::new(false),
+        }
+    }
+
+    pub fn snapshot(&self) -> StreamParams {
+        *self.params.lock().unwrap()
+    }
+
+    pub fn apply(&self, cmd: ControlCommand) {
+        let mut params = self.params.lock().unwrap();
+        match cmd {
+            ControlCommand::SetColorEnabled(enabled) => {
+                if params.color_enabled != enabled {
+                    params.color_enabled = enabled;
+                    *self.restart_requested.lock().unwrap() = true;
+                }
+            }
+            ControlCommand::SetJpegQuality { quality, subsamp } => {
+                params.jpeg_quality = quality;
+                params.jpeg_subsamp = subsamp;
+            }
+            ControlCommand::SetTargetFps(fps) => {
+                let fps = fps.clamp(MIN_TARGET_FPS, MAX_TARGET_FPS);
+                if params.target_fps != fps {
+                    params.target_fps = fps;
+                    *self.restart_requested.lock().unwrap() = true;
+                }
+            }
+            ControlCommand::SetDepthCompressionLevel(level) => {
+                // `level` comes straight off the wire from an operator query;
+                // clamp it to zstd's documented range so a bogus value can't
+                // reach `copy_encode` and panic the depth worker.
+                params.depth_zstd_level = level.clamp(MIN_DEPTH_ZSTD_LEVEL, MAX_DEPTH_ZSTD_LEVEL);
+            }
+        }
+    }
+
+    /// Take the pending-restart flag, if any. Meant to be polled once per
+    /// capture-thread iteration; a `true` result means the caller should
+    /// rebuild the pipeline from the current `snapshot()`.
+    pub fn take_restart_request(&self) -> bool {
+        let mut restart = self.restart_requested.lock().unwrap();
+        std::mem::take(&mut *restart)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggling_color_enabled_requests_a_restart() {
+        let state = ControlState::new(StreamParams::default());
+        assert!(!state.take_restart_request());
+        state.apply(ControlCommand::SetColorEnabled(false));
+        assert!(!state.snapshot().color_enabled);
+        assert!(state.take_restart_request());
+        // the flag is consumed by take_restart_request, not re-armed by a read
+        assert!(!state.take_restart_request());
+    }
+
+    #[test]
+    fn setting_the_same_color_enabled_value_does_not_request_a_restart() {
+        let state = ControlState::new(StreamParams::default());
+        state.apply(ControlCommand::SetColorEnabled(true)); // already true by default
+        assert!(!state.take_restart_request());
+    }
+
+    #[test]
+    fn quality_and_compression_changes_do_not_request_a_restart() {
+        let state = ControlState::new(StreamParams::default());
+        state.apply(ControlCommand::SetJpegQuality {
+            quality: 50,
+            subsamp: JpegSubsampling::Sub444,
+        });
+        state.apply(ControlCommand::SetDepthCompressionLevel(10));
+        assert_eq!(state.snapshot().jpeg_quality, 50);
+        assert_eq!(state.snapshot().depth_zstd_level, 10);
+        assert!(!state.take_restart_request());
+    }
+
+    #[test]
+    fn depth_compression_level_is_clamped_to_zstds_valid_range() {
+        let state = ControlState::new(StreamParams::default());
+        state.apply(ControlCommand::SetDepthCompressionLevel(1_000));
+        assert_eq!(state.snapshot().depth_zstd_level, MAX_DEPTH_ZSTD_LEVEL);
+        state.apply(ControlCommand::SetDepthCompressionLevel(-1_000));
+        assert_eq!(state.snapshot().depth_zstd_level, MIN_DEPTH_ZSTD_LEVEL);
+    }
+
+    #[test]
+    fn target_fps_is_clamped_to_a_sane_range() {
+        let state = ControlState::new(StreamParams::default());
+        state.apply(ControlCommand::SetTargetFps(10_000));
+        assert_eq!(state.snapshot().target_fps, MAX_TARGET_FPS);
+        assert!(state.take_restart_request());
+        state.apply(ControlCommand::SetTargetFps(0));
+        assert_eq!(state.snapshot().target_fps, MIN_TARGET_FPS);
+        assert!(state.take_restart_request());
+    }
+}
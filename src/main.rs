@@ -7,20 +7,73 @@
 
 use anyhow::{ensure, Result};
 use realsense_rust::{
-    base::Rs2Intrinsics, config::Config, context::Context, frame::{AccelFrame, ColorFrame, DepthFrame, FrameEx, GyroFrame, PoseFrame}, kind::{Rs2CameraInfo, Rs2Format, Rs2Option, Rs2ProductLine, Rs2StreamKind}, pipeline::InactivePipeline
+    config::Config,
+    context::Context,
+    frame::{AccelFrame, ColorFrame, DepthFrame, FrameEx, GyroFrame, PoseFrame},
+    kind::{Rs2CameraInfo, Rs2Format, Rs2Option, Rs2ProductLine, Rs2StreamKind},
+    pipeline::InactivePipeline,
 };
-use tokio::net::unix::pipe;
-use zenoh_types::{get_data_from_pixel, ColorFrameSerializable, CombinedFrameWire, DepthFrameSerializable, MotionFrameData};
+use snap::raw::Encoder;
 use std::{
     collections::HashSet,
     convert::TryFrom,
+    ffi::CString,
     io::{self, Write},
+    sync::Arc,
     time::Duration,
 };
-use snap::raw::Encoder;
+use tokio::net::unix::pipe;
+use zenoh_types::{
+    domain_timestamp, frame_hardware_timestamp, get_data_from_pixel, ColorFrameSerializable,
+    CombinedFrameWire, DepthFrameSerializable, Intrinsics, MotionFrameData, TimestampMode,
+};
 
+mod align;
+mod color_stream;
+mod control;
+mod pipeline_worker;
 mod zenoh_types;
 
+use align::DepthColorAligner;
+use color_stream::{ColorCodecMode, StreamingColorEncoder};
+use control::{ControlCommand, ControlState, StreamParams};
+use pipeline_worker::{FrameHandoff, FrameQueue};
+
+/// How many combined (depth+color) framesets the capture thread may buffer
+/// ahead of the encode/publish worker before it starts dropping the oldest.
+const COMBINED_QUEUE_CAPACITY: usize = 2;
+/// Motion samples are cheap to encode, so we can afford to buffer more of
+/// them before dropping any.
+const MOTION_QUEUE_CAPACITY: usize = 16;
+/// Depth and color run down independent queues in `Vp9` mode, so toggling
+/// `color_enabled` can't starve depth publication.
+const DEPTH_QUEUE_CAPACITY: usize = 2;
+const COLOR_QUEUE_CAPACITY: usize = 2;
+
+/// A gyro+accel reading, already pulled out of the RealSense frame types so
+/// it can cross the capture→worker handoff as plain, trivially `Send` data.
+struct MotionSample {
+    gyro: [f32; 3],
+    accel: [f32; 3],
+    timestamp: f64,
+}
+
+/// Run the hardware depth→color align processing block on every frameset
+/// before serializing it. Costs an extra processing pass per frame but makes
+/// `depth_zstd` and `rgb_jpeg` share one image plane and one set of
+/// intrinsics.
+const ALIGN_DEPTH_TO_COLOR: bool = true;
+
+/// How the color stream is published on `camera/color`. `Vp9` trades a small
+/// amount of latency for much less bandwidth on static scenes; `Jpeg` is kept
+/// for latency-sensitive use.
+const COLOR_CODEC_MODE: ColorCodecMode = ColorCodecMode::Vp9;
+
+/// Which clock domain `domain_timestamp` on the wire is drawn from. `System`
+/// keeps today's behavior; `Global` and `Hardware` require the matching
+/// `Rs2Option::GlobalTimeEnabled` setting, applied by `configure_timestamp_domain`.
+const TIMESTAMP_MODE: TimestampMode = TimestampMode::System;
+
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
     let session = zenoh::open(zenoh::Config::default()).await.unwrap();
@@ -35,87 +88,477 @@ async fn main() -> Result<(), anyhow::Error> {
     // create pipeline
     let pipeline = InactivePipeline::try_from(&context).unwrap();
 
-    let mut config = Config::new();
-
-
     // Check the USB speed of our connection
     // CStr => str => f32
     let usb_cstr = devices[0].info(Rs2CameraInfo::UsbTypeDescriptor).unwrap();
     let usb_val: f32 = usb_cstr.to_str().unwrap().parse().unwrap();
+    let serial: CString = devices[0]
+        .info(Rs2CameraInfo::SerialNumber)
+        .unwrap()
+        .to_owned();
+
+    // Runtime-tunable streaming parameters, written to by the `camera/control`
+    // queryable registered below and read by the capture thread and the
+    // encode/publish workers on every iteration.
+    let control_state = Arc::new(ControlState::new(StreamParams::default()));
+
+    let initial_params = control_state.snapshot();
+    let config = build_config(&serial, usb_val, initial_params)?;
+
+    // Change pipeline's type from InactivePipeline -> ActivePipeline
+    let mut pipeline = pipeline.start(Some(config)).unwrap();
+    configure_timestamp_domain(&devices[0], TIMESTAMP_MODE)?;
+
+    let mut aligner = if ALIGN_DEPTH_TO_COLOR {
+        Some(DepthColorAligner::new()?)
+    } else {
+        None
+    };
+
+    // Pull intrinsics from the first frameset and publish them once on a
+    // latched key so a subscriber can deproject pixels into metric 3-D points
+    // without waiting on a combined frame (mirrors ROS's CameraInfo topic).
+    // This has to run through `aligner` first: once depth is aligned to
+    // color, every depth buffer the capture thread emits shares the color
+    // sensor's image plane, so the published intrinsics need to come from
+    // the color stream's profile rather than the depth stream's -- otherwise
+    // `Intrinsics::deproject` would use the wrong fx/fy/ppx/ppy for every
+    // aligned frame.
+    //
+    // USB2 devices never enable Color at all (see `build_config`), so a
+    // color frame may simply never arrive here; only require one when the
+    // USB speed says Color was actually requested.
+    let depth_scale = depth_scale_meters(&devices[0])?;
+    let color_enabled = usb_val >= 3.0;
+    let (intrinsics, color_width, color_height) = {
+        let timeout = Duration::from_millis(2000);
+        let frames = pipeline.wait(Some(timeout))?;
+        let frames = match aligner.as_mut() {
+            Some(aligner) => aligner.align(frames)?,
+            None => frames,
+        };
+        let mut depth_frames = frames.frames_of_type::<DepthFrame>();
+        let mut rgb_frames = frames.frames_of_type::<ColorFrame>();
+        ensure!(
+            !depth_frames.is_empty(),
+            "No depth frame received while probing intrinsics"
+        );
+        if color_enabled {
+            ensure!(
+                !rgb_frames.is_empty(),
+                "No color frame received while probing stream resolution"
+            );
+        }
+        let depth_frame = depth_frames.pop().unwrap();
+        let rgb_frame = rgb_frames.pop();
+        let intrinsics = match (&rgb_frame, aligner.is_some()) {
+            (Some(rgb_frame), true) => {
+                Intrinsics::from_rs2(&rgb_frame.stream_profile().intrinsics()?, depth_scale)
+            }
+            _ => Intrinsics::from_rs2(&depth_frame.stream_profile().intrinsics()?, depth_scale),
+        };
+        let (color_width, color_height) = match &rgb_frame {
+            Some(rgb_frame) => (rgb_frame.width() as u32, rgb_frame.height() as u32),
+            // No color stream on this device; the VP9/JPEG encoder still
+            // gets constructed below but its queue simply stays empty.
+            None => (640, 480),
+        };
+        (intrinsics, color_width, color_height)
+    };
+    session
+        .put("camera/intrinsics", intrinsics.encode())
+        .await
+        .map_err(|e| anyhow::anyhow!(e))?;
+    // Keep answering `camera/intrinsics` queries so a subscriber that joins
+    // after the one-shot put above can still fetch them.
+    let _intrinsics_queryable = session
+        .declare_queryable("camera/intrinsics")
+        .callback(move |query| {
+            if let Err(e) = query.reply("camera/intrinsics", intrinsics.encode()) {
+                eprintln!("camera/intrinsics reply failed: {e}");
+            }
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!(e))?;
+
+    // VP9/JPEG color encoder, plus a subscriber that lets a newly-joined or
+    // loss-recovering peer ask for a fresh keyframe instead of waiting out the
+    // encoder's normal keyframe interval.
+    let (mut color_encoder, keyframe_request) =
+        StreamingColorEncoder::new(COLOR_CODEC_MODE, color_width, color_height)?;
+    let _keyframe_sub = session
+        .declare_subscriber("camera/color/request_keyframe")
+        .callback(move |_sample| keyframe_request.request())
+        .await
+        .map_err(|e| anyhow::anyhow!(e))?;
+
+    // Operator-facing control surface: decode a `ControlCommand` off each
+    // query and apply it to the shared `ControlState`. `color_enabled` and
+    // `target_fps` changes are picked up by the capture thread's
+    // restart-polling loop below; quality/compression changes apply on the
+    // next frame each worker encodes.
+    let _control_queryable = {
+        let control_state = control_state.clone();
+        session
+            .declare_queryable("camera/control")
+            .callback(move |query| {
+                let result = query
+                    .payload()
+                    .map(|payload| payload.to_bytes().into_owned())
+                    .ok_or_else(|| anyhow::anyhow!("camera/control query carried no payload"))
+                    .and_then(|bytes| ControlCommand::decode(&bytes));
+                match result {
+                    Ok(cmd) => {
+                        control_state.apply(cmd);
+                        if let Err(e) = query.reply("camera/control", "ok") {
+                            eprintln!("camera/control reply failed: {e}");
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("camera/control decode failed: {e}");
+                        if let Err(e) = query.reply_err(e.to_string()) {
+                            eprintln!("camera/control error reply failed: {e}");
+                        }
+                    }
+                }
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))?
+    };
+
+    // Capture thread: only pulls framesets off the sensor and hands them to
+    // bounded queues. It never touches the network or a compressor, so
+    // encode/publish slowness can't turn into a pipeline.wait timeout.
+    //
+    // `combined_queue` only feeds the legacy `Jpeg` path, which bundles depth
+    // and color into one message and so needs both present at once. `Vp9`
+    // mode instead uses `depth_queue`/`color_queue`, pushed to independently,
+    // so disabling color (via a pipeline restart below) can't starve depth
+    // publication.
+    let combined_queue =
+        FrameQueue::<FrameHandoff<(DepthFrame, ColorFrame)>>::new(COMBINED_QUEUE_CAPACITY);
+    let depth_queue = FrameQueue::<FrameHandoff<DepthFrame>>::new(DEPTH_QUEUE_CAPACITY);
+    let color_queue = FrameQueue::<FrameHandoff<ColorFrame>>::new(COLOR_QUEUE_CAPACITY);
+    let motion_queue = FrameQueue::<MotionSample>::new(MOTION_QUEUE_CAPACITY);
+    {
+        let combined_queue = combined_queue.clone();
+        let depth_queue = depth_queue.clone();
+        let color_queue = color_queue.clone();
+        let motion_queue = motion_queue.clone();
+        let control_state = control_state.clone();
+        let serial = serial.clone();
+        // `pipeline`, `aligner` and `context` own librealsense2 handles; hand
+        // them to the capture thread the same way a captured frame gets
+        // handed to a worker -- ownership moves once, nothing touches them
+        // concurrently. `context` only needs to come along so a failed
+        // restart (see below) can rebuild an `InactivePipeline` from scratch.
+        let capture_state = FrameHandoff(((pipeline, aligner), context));
+        std::thread::spawn(move || {
+            let FrameHandoff(((mut pipeline, mut aligner), context)) = capture_state;
+            // Last config the device actually accepted; falls back to this
+            // on a failed restart instead of leaving the thread with no
+            // pipeline at all.
+            let mut current_params = initial_params;
+            loop {
+                if control_state.take_restart_request() {
+                    let params = control_state.snapshot();
+                    match build_config(&serial, usb_val, params) {
+                        Ok(new_config) => match pipeline.stop().start(Some(new_config)) {
+                            Ok(restarted) => {
+                                pipeline = restarted;
+                                current_params = params;
+                            }
+                            Err(e) => {
+                                // `start` consumed the `InactivePipeline`, so
+                                // there's nothing left to retry with -- the
+                                // device rejected the new settings outright
+                                // (e.g. an unsupported fps), not a transient
+                                // hiccup. Re-derive a fresh `InactivePipeline`
+                                // from `context` and fall back to the last
+                                // config the device is known to accept,
+                                // rather than panicking the capture thread
+                                // and leaving the workers idling forever.
+                                eprintln!(
+                                    "pipeline restart with new stream config failed: {e}; \
+                                     reverting to the last working configuration"
+                                );
+                                let fallback_config =
+                                    build_config(&serial, usb_val, current_params)
+                                        .expect("rebuild last working stream config");
+                                pipeline = InactivePipeline::try_from(&context)
+                                    .expect("re-create inactive pipeline after failed restart")
+                                    .start(Some(fallback_config))
+                                    .expect("restart pipeline with last working stream config");
+                            }
+                        },
+                        Err(e) => eprintln!("failed to build restart config: {e}"),
+                    }
+                }
 
+                let timeout = Duration::from_millis(500);
+                let frames = match pipeline.wait(Some(timeout)) {
+                    Ok(frames) => frames,
+                    Err(e) => {
+                        eprintln!("pipeline.wait failed: {e}");
+                        continue;
+                    }
+                };
+                let frames = match aligner.as_mut() {
+                    Some(aligner) => match aligner.align(frames) {
+                        Ok(frames) => frames,
+                        Err(e) => {
+                            eprintln!("depth/color align failed: {e}");
+                            continue;
+                        }
+                    },
+                    None => frames,
+                };
+
+                let mut depth_frames = frames.frames_of_type::<DepthFrame>();
+                let mut rgb_frames = frames.frames_of_type::<ColorFrame>();
+                match COLOR_CODEC_MODE {
+                    ColorCodecMode::Jpeg => {
+                        if !depth_frames.is_empty() && !rgb_frames.is_empty() {
+                            let depth_frame = depth_frames.pop().unwrap();
+                            let rgb_frame = rgb_frames.pop().unwrap();
+                            combined_queue.push(FrameHandoff((depth_frame, rgb_frame)));
+                        }
+                    }
+                    ColorCodecMode::Vp9 => {
+                        if let Some(depth_frame) = depth_frames.pop() {
+                            depth_queue.push(FrameHandoff(depth_frame));
+                        }
+                        if let Some(rgb_frame) = rgb_frames.pop() {
+                            color_queue.push(FrameHandoff(rgb_frame));
+                        }
+                    }
+                }
+
+                let gyro_frames = frames.frames_of_type::<GyroFrame>();
+                let accel_frames = frames.frames_of_type::<AccelFrame>();
+                if !gyro_frames.is_empty() && !accel_frames.is_empty() {
+                    motion_queue.push(MotionSample {
+                        gyro: *gyro_frames[0].rotational_velocity(),
+                        accel: *accel_frames[0].acceleration(),
+                        timestamp: gyro_frames[0].timestamp(),
+                    });
+                }
+            }
+        });
+    }
+
+    // Combined-frame worker: legacy `Jpeg` path only. Drains `combined_queue`
+    // and does the JPEG + zstd compression that used to sit inline in the
+    // capture loop. `Vp9` mode never pushes to `combined_queue`, so this
+    // simply idles when that mode is selected.
+    //
+    // The pop and the encode both happen inside `spawn_blocking`, and only
+    // the already-`Send` encoded bytes cross back out to the `async` task --
+    // `DepthFrame`/`ColorFrame` wrap a librealsense2 handle that isn't `Send`
+    // (see `pipeline_worker::HandoffSafe`), so one must never be held across
+    // an `.await` inside a `tokio::spawn`'d future on the default
+    // multi-threaded runtime.
+    let depth_control_state = control_state.clone();
+    let color_control_state = control_state.clone();
+    let combined_worker = {
+        let session = session.clone();
+        let combined_queue = combined_queue.clone();
+        let control_state = control_state.clone();
+        tokio::spawn(async move {
+            loop {
+                let combined_queue = combined_queue.clone();
+                let control_state = control_state.clone();
+                let encoded = tokio::task::spawn_blocking(move || {
+                    let FrameHandoff((depth_frame, rgb_frame)) = combined_queue.pop_blocking();
+                    let params = control_state.snapshot();
+                    CombinedFrameWire::from_frames(
+                        &depth_frame,
+                        &rgb_frame,
+                        intrinsics,
+                        TIMESTAMP_MODE,
+                        params.depth_zstd_level,
+                        params.jpeg_quality,
+                        params.jpeg_subsamp.to_turbojpeg(),
+                    )
+                    .encode()
+                })
+                .await
+                .expect("combined-frame worker thread panicked");
+
+                println!("sending frame...");
+                if let Err(e) = session.put("camera/combined", encoded).await {
+                    eprintln!("combined-frame publish failed: {e}");
+                }
+            }
+        })
+    };
+
+    // Depth worker: `Vp9` path only. Drains `depth_queue` independently of
+    // color, at whatever zstd level the operator last set. As with
+    // `combined_worker`, the `DepthFrame` never leaves the `spawn_blocking`
+    // closure.
+    let depth_worker = {
+        let session = session.clone();
+        let depth_queue = depth_queue.clone();
+        let control_state = depth_control_state.clone();
+        tokio::spawn(async move {
+            loop {
+                let depth_queue = depth_queue.clone();
+                let control_state = control_state.clone();
+                let encoded = tokio::task::spawn_blocking(move || {
+                    let FrameHandoff(depth_frame) = depth_queue.pop_blocking();
+                    let params = control_state.snapshot();
+                    let capture_timestamp = depth_frame.timestamp();
+                    let domain_ts = domain_timestamp(
+                        capture_timestamp,
+                        frame_hardware_timestamp(&depth_frame),
+                        TIMESTAMP_MODE,
+                    );
+                    let depth_serializable = DepthFrameSerializable::new(&depth_frame, domain_ts);
+                    depth_serializable.encodeAndCompress(params.depth_zstd_level)
+                })
+                .await
+                .expect("depth worker thread panicked");
+
+                if let Err(e) = session.put("camera/depth", encoded).await {
+                    eprintln!("depth publish failed: {e}");
+                }
+            }
+        })
+    };
+
+    // Color worker: `Vp9` path only. Drains `color_queue` independently of
+    // depth, at whatever JPEG quality/subsampling the operator last set (VP9
+    // itself ignores those, see `StreamingColorEncoder::encode`). Unlike the
+    // other two workers, `color_encoder` carries state across frames (VP9's
+    // inter-frame history), so each iteration moves it into `spawn_blocking`
+    // and takes it back out alongside the encoded chunks, rather than
+    // leaving it captured by reference across the `.await`.
+    let color_worker = {
+        let session = session.clone();
+        let color_queue = color_queue.clone();
+        let control_state = color_control_state.clone();
+        let mut color_encoder = color_encoder;
+        tokio::spawn(async move {
+            loop {
+                let color_queue = color_queue.clone();
+                let control_state = control_state.clone();
+                let (result, encoder) = tokio::task::spawn_blocking(move || {
+                    let FrameHandoff(rgb_frame) = color_queue.pop_blocking();
+                    let params = control_state.snapshot();
+                    let result = color_encoder.encode(
+                        &rgb_frame,
+                        params.jpeg_quality,
+                        params.jpeg_subsamp.to_turbojpeg(),
+                    );
+                    (result, color_encoder)
+                })
+                .await
+                .expect("color worker thread panicked");
+                color_encoder = encoder;
+
+                match result {
+                    Ok(chunks) => {
+                        for chunk in chunks {
+                            if let Err(e) = session.put("camera/color", chunk.encode()).await {
+                                eprintln!("color publish failed: {e}");
+                            }
+                        }
+                    }
+                    Err(e) => eprintln!("color encode failed: {e}"),
+                }
+            }
+        })
+    };
+
+    // Motion worker: drains `motion_queue` independently of the much heavier
+    // combined-frame path above.
+    let motion_worker = {
+        let session = session.clone();
+        tokio::spawn(async move {
+            loop {
+                let sample = {
+                    let motion_queue = motion_queue.clone();
+                    tokio::task::spawn_blocking(move || motion_queue.pop_blocking())
+                        .await
+                        .expect("motion worker thread panicked")
+                };
+                let motion_frame_data =
+                    MotionFrameData::new(sample.gyro, sample.accel, sample.timestamp);
+                let encoded_motion = motion_frame_data.encodeAndCompress();
+                if let Err(e) = session.put("camera/motion", encoded_motion).await {
+                    eprintln!("motion publish failed: {e}");
+                }
+            }
+        })
+    };
+
+    let _ = tokio::try_join!(combined_worker, depth_worker, color_worker, motion_worker)?;
+    Ok(())
+}
+
+/// Build a `Config` for this device's USB speed and the current `StreamParams`.
+/// Used both for the initial pipeline start and to rebuild the pipeline when
+/// the capture thread sees a pending restart request (`color_enabled` or
+/// `target_fps` changed). USB2 devices can't keep up with a Depth+Color
+/// combination, so they get Infrared instead of Color regardless of
+/// `color_enabled`.
+fn build_config(serial: &CString, usb_val: f32, params: StreamParams) -> anyhow::Result<Config> {
+    let mut config = Config::new();
+    let fps = params.target_fps;
     if usb_val >= 3.0 {
         config
-            .enable_device_from_serial(devices[0].info(Rs2CameraInfo::SerialNumber).unwrap())?
+            .enable_device_from_serial(serial)?
             .disable_all_streams()?
-            .enable_stream(Rs2StreamKind::Depth, None, 640, 0, Rs2Format::Z16, 30)?
-            .enable_stream(Rs2StreamKind::Color, None, 640, 0, Rs2Format::Rgb8, 30)?
+            .enable_stream(Rs2StreamKind::Depth, None, 640, 0, Rs2Format::Z16, fps)?
             .enable_stream(Rs2StreamKind::Gyro, None, 0, 0, Rs2Format::Any, 0)?
             .enable_stream(Rs2StreamKind::Accel, None, 0, 0, Rs2Format::Any, 0)?;
+        if params.color_enabled {
+            config.enable_stream(Rs2StreamKind::Color, None, 640, 0, Rs2Format::Rgb8, fps)?;
+        }
     } else {
         config
-            .enable_device_from_serial(devices[0].info(Rs2CameraInfo::SerialNumber).unwrap())?
+            .enable_device_from_serial(serial)?
             .disable_all_streams()?
-            .enable_stream(Rs2StreamKind::Depth, None, 640, 0, Rs2Format::Z16, 30)?
-            .enable_stream(Rs2StreamKind::Infrared, Some(1), 640, 0, Rs2Format::Y8, 30)?
+            .enable_stream(Rs2StreamKind::Depth, None, 640, 0, Rs2Format::Z16, fps)?
+            .enable_stream(Rs2StreamKind::Infrared, Some(1), 640, 0, Rs2Format::Y8, fps)?
             .enable_stream(Rs2StreamKind::Gyro, None, 0, 0, Rs2Format::Any, 0)?
             .enable_stream(Rs2StreamKind::Accel, None, 0, 0, Rs2Format::Any, 0)?;
-
     }
+    Ok(config)
+}
 
-    // Change pipeline's type from InactivePipeline -> ActivePipeline
-    let mut pipeline = pipeline.start(Some(config)).unwrap();
-    enable_system_time(&devices[0])?;
-    let mut gyro = [0.0, 0.0, 0.0];
-    let mut accel = [0.0, 0.0, 0.0];
-
-    // process frames
-    loop {
-        let timeout = Duration::from_millis(500);
-        let frames = pipeline.wait(Some(timeout))?;
-
-        // Get depth
-        let mut depth_frames = frames.frames_of_type::<DepthFrame>();
-        let mut rgb_frame = frames.frames_of_type::<ColorFrame>();
-        println!("{} {}", depth_frames.is_empty(), rgb_frame.is_empty());
-        if !depth_frames.is_empty() &&  !rgb_frame.is_empty() {
-            let depth_frame = depth_frames.pop().unwrap();
-            let rgb_frame = rgb_frame.pop().unwrap();
-            // let timestamp = depth_frame.timestamp();
-            // let depth_serializable = DepthFrameSerializable::new(&depth_frame, timestamp);
-            // let encoded_depth = depth_serializable.encodeAndCompress();
-            // let timestamp = rgb_frame.timestamp();
-            // let rgb_serializable = ColorFrameSerializable::new(&rgb_frame, timestamp);
-            // let encoded_rgb = rgb_serializable.encodeAndCompress();
-            let combined_frame = CombinedFrameWire::from_frames(&depth_frame, &rgb_frame);
-            // session.put("camera/rgb", encoded_rgb).await.map_err(|e| anyhow::anyhow!(e))?;
-            // session.put("camera/depth", encoded_depth).await.map_err(|e| anyhow::anyhow!(e))?;
-            println!("sending frame...");
-            session.put("camera/combined", combined_frame.encode()).await.map_err(|e| anyhow::anyhow!(e))?;
-        }
-
-        // Get gyro
-        let gyro_frames = frames.frames_of_type::<GyroFrame>();
-        let accel_frames = frames.frames_of_type::<AccelFrame>();
-
-        if !gyro_frames.is_empty() && !accel_frames.is_empty() {
-            gyro = *gyro_frames[0].rotational_velocity();
-            accel = *accel_frames[0].acceleration();
-            let timestamp = gyro_frames[0].timestamp();
-            let motion_frame_data = MotionFrameData::new(gyro, accel, timestamp);
-            let encoded_motion = motion_frame_data.encodeAndCompress();
-            session.put("camera/motion", encoded_motion).await.map_err(|e| anyhow::anyhow!(e))?;
+/// Metres per depth unit, e.g. to turn a raw Z16 value into metres. RealSense
+/// exposes this per depth sensor rather than per stream, so we just take the
+/// first sensor that supports it.
+fn depth_scale_meters(device: &realsense_rust::device::Device) -> anyhow::Result<f32> {
+    for sensor in device.sensors() {
+        if let Ok(scale) = sensor.get_option(Rs2Option::DepthUnits) {
+            return Ok(scale);
         }
-
     }
-
+    Ok(0.001) // RealSense's usual default: 1 mm per unit
 }
 
-fn enable_system_time(device: &realsense_rust::device::Device) -> anyhow::Result<()> {
+/// Put every sensor's `GlobalTimeEnabled` option into the state `mode`
+/// expects. `Hardware` mode still disables it: the hardware clock is read out
+/// of frame metadata rather than the SDK-reported timestamp, so it doesn't
+/// need RealSense's host-time correction.
+fn configure_timestamp_domain(
+    device: &realsense_rust::device::Device,
+    mode: TimestampMode,
+) -> anyhow::Result<()> {
+    let global_time_enabled = match mode {
+        TimestampMode::System | TimestampMode::Hardware => 0.0,
+        TimestampMode::Global => 1.0,
+    };
     for mut sensor in device.sensors() {
-        // 0.0 = SYSTEM_TIME, 1.0 = GLOBAL_TIME
-        sensor.set_option(Rs2Option::GlobalTimeEnabled, 0.0)?;   // ← key line
-        println!("{} global-time-enabled = {}", sensor.info(Rs2CameraInfo::Name).unwrap().to_str().unwrap(), sensor.get_option(Rs2Option::GlobalTimeEnabled).unwrap()); // should print 0
-
+        sensor.set_option(Rs2Option::GlobalTimeEnabled, global_time_enabled)?;
+        println!(
+            "{} global-time-enabled = {}",
+            sensor.info(Rs2CameraInfo::Name).unwrap().to_str().unwrap(),
+            sensor.get_option(Rs2Option::GlobalTimeEnabled).unwrap()
+        );
     }
     Ok(())
 }
@@ -0,0 +1,117 @@
+//! Decouples RealSense frame capture from the much slower encode+publish
+//! path. Previously the main loop did `pipeline.wait`, JPEG/zstd compression
+//! and the async `session.put` all inline, so encode latency stalled the
+//! next capture and could trip a RealSense frame-wait timeout. Here a single
+//! capture thread only pulls framesets off the sensor and hands them to a
+//! bounded [`FrameQueue`] per stream group; separate encode/publish workers
+//! drain those queues on their own schedule. If a worker falls behind, the
+//! queue drops the oldest entry instead of growing unbounded, so a slow
+//! encoder bounds added latency rather than piling up backlog.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+
+use realsense_rust::{
+    context::Context,
+    frame::{ColorFrame, DepthFrame},
+    pipeline::ActivePipeline,
+};
+
+use crate::align::DepthColorAligner;
+
+/// Marks a librealsense2-backed handle as safe to move across the
+/// capture-thread -> worker handoff in [`FrameHandoff`], despite not being
+/// `Send` on its own. librealsense2 frame and pipeline handles are
+/// `shared_ptr`s to a C object whose refcount is updated atomically
+/// (`std::atomic` in upstream `rs_frame.hpp`/`rs_pipeline.hpp`); neither
+/// object's documented contract claims thread affinity for drop/use, only
+/// that concurrent access needs external synchronization -- which is exactly
+/// what exclusive, one-at-a-time ownership transfer through `FrameHandoff`
+/// already gives it. Only implement this for the specific handle types that
+/// contract applies to, not blanket over every `T`, so an unrelated
+/// non-`Send` type can't ride along by accident.
+///
+/// # Safety
+/// Only implement for types whose non-`Send`-ness comes solely from wrapping
+/// a librealsense2 handle with atomically-refcounted, thread-affinity-free
+/// internals, as described above.
+pub unsafe trait HandoffSafe {}
+
+unsafe impl HandoffSafe for DepthFrame {}
+unsafe impl HandoffSafe for ColorFrame {}
+unsafe impl HandoffSafe for ActivePipeline {}
+unsafe impl HandoffSafe for Context {}
+unsafe impl HandoffSafe for DepthColorAligner {}
+unsafe impl<T: HandoffSafe> HandoffSafe for Option<T> {}
+unsafe impl<A: HandoffSafe, B: HandoffSafe> HandoffSafe for (A, B) {}
+
+/// Carries a [`HandoffSafe`] value across the capture-thread -> worker
+/// handoff -- captured on the capture thread, then handed off whole to
+/// exactly one worker, never touched concurrently.
+pub struct FrameHandoff<T: HandoffSafe>(pub T);
+unsafe impl<T: HandoffSafe> Send for FrameHandoff<T> {}
+
+/// A fixed-capacity queue that drops the oldest item instead of blocking the
+/// producer once full. The capture thread must never stall waiting on a
+/// worker, so `push` always succeeds immediately.
+pub struct FrameQueue<T> {
+    capacity: usize,
+    state: Mutex<VecDeque<T>>,
+    not_empty: Condvar,
+}
+
+impl<T> FrameQueue<T> {
+    pub fn new(capacity: usize) -> Arc<Self> {
+        Arc::new(Self {
+            capacity,
+            state: Mutex::new(VecDeque::with_capacity(capacity)),
+            not_empty: Condvar::new(),
+        })
+    }
+
+    pub fn push(&self, item: T) {
+        let mut queue = self.state.lock().unwrap();
+        if queue.len() >= self.capacity {
+            queue.pop_front(); // drop the oldest frame to bound latency
+        }
+        queue.push_back(item);
+        self.not_empty.notify_one();
+    }
+
+    /// Block the calling thread until an item is available. Meant to be
+    /// called from `tokio::task::spawn_blocking`, not directly on an async
+    /// worker task.
+    pub fn pop_blocking(&self) -> T {
+        let mut queue = self.state.lock().unwrap();
+        loop {
+            if let Some(item) = queue.pop_front() {
+                return item;
+            }
+            queue = self.not_empty.wait(queue).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_past_capacity_drops_the_oldest_item() {
+        let queue = FrameQueue::<i32>::new(2);
+        queue.push(1);
+        queue.push(2);
+        queue.push(3); // 1 should be dropped, not 2
+        assert_eq!(queue.pop_blocking(), 2);
+        assert_eq!(queue.pop_blocking(), 3);
+    }
+
+    #[test]
+    fn pop_blocking_returns_items_in_fifo_order() {
+        let queue = FrameQueue::<i32>::new(4);
+        queue.push(1);
+        queue.push(2);
+        assert_eq!(queue.pop_blocking(), 1);
+        assert_eq!(queue.pop_blocking(), 2);
+    }
+}
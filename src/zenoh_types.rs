@@ -1,7 +1,15 @@
 use bincode::{Decode, Encode};
-use realsense_rust::{frame::{ColorFrame, DepthFrame, FrameEx, ImageFrame, PixelKind}, kind};
-use serde::{Serialize, Deserialize};
-use turbojpeg::{compress_image, decompress_image, image::{ImageBuffer, Rgb}, OwnedBuf, PixelFormat, Subsamp};
+use realsense_rust::{
+    base::Rs2Intrinsics,
+    frame::{ColorFrame, DepthFrame, FrameEx, ImageFrame, PixelKind},
+    kind::{self, Rs2FrameMetadata},
+};
+use serde::{Deserialize, Serialize};
+use turbojpeg::{
+    compress_image, decompress_image,
+    image::{ImageBuffer, Rgb},
+    OwnedBuf, PixelFormat, Subsamp,
+};
 use zstd::stream::{copy_encode, decode_all, encode_all};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Encode, Decode)]
@@ -13,7 +21,11 @@ pub struct CombinedFrame {
 
 impl CombinedFrame {
     pub fn new(rgb: ColorFrameSerializable, depth: DepthFrameSerializable, timestamp: f64) -> Self {
-        Self { rgb, depth, timestamp }
+        Self {
+            rgb,
+            depth,
+            timestamp,
+        }
     }
 
     pub fn encodeAndCompress(&self) -> Vec<u8> {
@@ -25,7 +37,7 @@ impl CombinedFrame {
 
     pub fn decodeAndDecompress(encoded: Vec<u8>) -> Self {
         let (wire, _): (CombinedFrame, _) =
-        bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+            bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
         wire
     }
 }
@@ -38,15 +50,15 @@ pub enum ImageEncoding {
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, Encode, Decode)]
 pub struct RGB8Local {
-    b: u8,
-    g: u8,
-    r: u8,
+    pub(crate) b: u8,
+    pub(crate) g: u8,
+    pub(crate) r: u8,
 }
 
 const DEPTH_SCALE_FACTOR: u16 = 8738; // multiply by this to convert meters to u16
 const MINIMUM_DISTANCE_METERS: f32 = 0.5;
 
-pub fn encode_meters_to_u16(meters: f32)     -> u16 {
+pub fn encode_meters_to_u16(meters: f32) -> u16 {
     ((meters - MINIMUM_DISTANCE_METERS) * DEPTH_SCALE_FACTOR as f32) as u16
 }
 
@@ -54,7 +66,6 @@ pub fn decode_u16_to_meters(code: u16) -> f32 {
     (code as f32) / DEPTH_SCALE_FACTOR as f32 + MINIMUM_DISTANCE_METERS
 }
 
-
 #[derive(Serialize, Deserialize, Debug, Clone, Encode, Decode)]
 pub struct DepthFrameSerializable {
     pub width: usize,
@@ -63,7 +74,6 @@ pub struct DepthFrameSerializable {
     pub timestamp: f64,
 }
 
-
 #[derive(Serialize, Deserialize, Debug, Clone, Encode, Decode)]
 pub struct ColorFrameSerializable {
     pub width: usize,
@@ -78,7 +88,6 @@ pub struct ImageForWire {
     pub timestamp: f64,
 }
 
-
 impl DepthFrameSerializable {
     pub fn new(frame: &DepthFrame, timestamp: f64) -> Self {
         let mut data: Vec<u16> = Vec::new();
@@ -95,15 +104,16 @@ impl DepthFrameSerializable {
         }
     }
 
-    pub fn encodeAndCompress(&self) -> Vec<u8> {
+    /// `level` is the zstd compression level to use, runtime-tunable so an
+    /// operator can trade CPU for bandwidth without a restart.
+    pub fn encodeAndCompress(&self, level: i32) -> Vec<u8> {
         let encoded = bincode::encode_to_vec(&self, bincode::config::standard()).unwrap();
         let mut result = Vec::new();
-        copy_encode(&encoded[..], &mut result, 6).unwrap();
+        copy_encode(&encoded[..], &mut result, level).unwrap();
         result
     }
 }
 
-
 impl ColorFrameSerializable {
     pub fn new(frame: &ColorFrame, timestamp: f64) -> Self {
         let mut data: Vec<u8> = Vec::new();
@@ -114,7 +124,7 @@ impl ColorFrameSerializable {
                 data.push(px.g);
                 data.push(px.b);
             }
-        }   
+        }
 
         Self {
             width: frame.width(),
@@ -124,7 +134,13 @@ impl ColorFrameSerializable {
         }
     }
     pub fn encodeAndCompress(&self) -> Vec<u8> {
-        let jpeg = compress_image::<Rgb<u8>>(&ImageBuffer::from_vec(self.width as u32, self.height as u32, self.data.clone()).unwrap(), 75, Subsamp::Sub2x2).unwrap();
+        let jpeg = compress_image::<Rgb<u8>>(
+            &ImageBuffer::from_vec(self.width as u32, self.height as u32, self.data.clone())
+                .unwrap(),
+            75,
+            Subsamp::Sub2x2,
+        )
+        .unwrap();
         let envelope: ImageForWire = ImageForWire {
             image: jpeg.to_vec(),
             timestamp: self.timestamp,
@@ -134,35 +150,41 @@ impl ColorFrameSerializable {
     }
     pub fn decodeAndDecompress(encoded: Vec<u8>) -> (Vec<u8>, f64) {
         let (wire, _): (ImageForWire, _) =
-        bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+            bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
 
         // JPEG --------------------------------------------------------------
         debug_assert!(wire.image.starts_with(&[0xFF, 0xD8]));
         let rgb = turbojpeg::decompress_image::<Rgb<u8>>(&wire.image).unwrap();
 
         (rgb.to_vec(), wire.timestamp)
-
     }
 }
 
-pub fn get_data_from_pixel(pixel: PixelKind<'_>) ->RGB8Local {
+pub fn get_data_from_pixel(pixel: PixelKind<'_>) -> RGB8Local {
     match pixel {
-        PixelKind::Bgr8 { b, g, r } => RGB8Local { b: *b, g: *g, r: *r },
+        PixelKind::Bgr8 { b, g, r } => RGB8Local {
+            b: *b,
+            g: *g,
+            r: *r,
+        },
         _ => panic!("Unsupported pixel format"),
     }
 }
 
-
 #[derive(Serialize, Deserialize, Debug, Clone, Encode, Decode)]
 pub struct MotionFrameData {
-    pub gyro: [f32; 3], // rad/s
+    pub gyro: [f32; 3],  // rad/s
     pub accel: [f32; 3], // m/s^2
-    pub timestamp: f64, // seconds
+    pub timestamp: f64,  // seconds
 }
 
 impl MotionFrameData {
     pub fn new(gyro: [f32; 3], accel: [f32; 3], timestamp: f64) -> Self {
-        Self { gyro, accel, timestamp }
+        Self {
+            gyro,
+            accel,
+            timestamp,
+        }
     }
 
     pub fn encodeAndCompress(&self) -> Vec<u8> {
@@ -171,12 +193,185 @@ impl MotionFrameData {
     }
     pub fn decodeAndDecompress(encoded: Vec<u8>) -> Self {
         let (wire, _): (MotionFrameData, _) =
-        bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+            bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
         wire
     }
 }
 
+/// Mirrors librealsense2's `rs2_distortion` so we can serialize it over the wire.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum DistortionModel {
+    None,
+    ModifiedBrownConrady,
+    InverseBrownConrady,
+    FTheta,
+    BrownConrady,
+    KannalaBrandt4,
+}
 
+/// Camera intrinsics for a single stream, enough for a consumer to deproject a
+/// pixel + depth reading into a metric 3-D point (the same information ROS's
+/// `sensor_msgs/CameraInfo` carries alongside every image).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Encode, Decode)]
+pub struct Intrinsics {
+    pub width: u16,
+    pub height: u16,
+    pub ppx: f32,
+    pub ppy: f32,
+    pub fx: f32,
+    pub fy: f32,
+    pub model: DistortionModel,
+    pub coeffs: [f32; 5],
+    /// metres per depth unit, i.e. `Rs2Option::DepthUnits` on the depth sensor
+    pub depth_scale: f32,
+}
+
+impl Intrinsics {
+    pub fn from_rs2(intr: &Rs2Intrinsics, depth_scale: f32) -> Self {
+        let model = match format!("{:?}", intr.model).as_str() {
+            "ModifiedBrownConrady" => DistortionModel::ModifiedBrownConrady,
+            "InverseBrownConrady" => DistortionModel::InverseBrownConrady,
+            "FTheta" => DistortionModel::FTheta,
+            "BrownConrady" => DistortionModel::BrownConrady,
+            "KannalaBrandt4" => DistortionModel::KannalaBrandt4,
+            _ => DistortionModel::None,
+        };
+        Self {
+            width: intr.width as u16,
+            height: intr.height as u16,
+            ppx: intr.ppx,
+            ppy: intr.ppy,
+            fx: intr.fx,
+            fy: intr.fy,
+            model,
+            coeffs: intr.coeffs,
+            depth_scale,
+        }
+    }
+
+    /// Pinhole-inverse: turn a depth pixel `(u, v)` plus its depth reading in
+    /// metres into a metric 3-D point in the camera's optical frame.
+    pub fn deproject(&self, u: f32, v: f32, depth_m: f32) -> [f32; 3] {
+        let x = (u - self.ppx) / self.fx;
+        let y = (v - self.ppy) / self.fy;
+        [depth_m * x, depth_m * y, depth_m]
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        bincode::encode_to_vec(self, bincode::config::standard()).unwrap()
+    }
+
+    pub fn decode(buf: &[u8]) -> Self {
+        let (intr, _) = bincode::decode_from_slice(buf, bincode::config::standard()).unwrap();
+        intr
+    }
+}
+
+/// Which clock domain `domain_timestamp` is drawn from. Consumers that need
+/// to compensate for clock skew or fuse against another sensor's timebase can
+/// pick the one that makes that job easiest, rather than being stuck with
+/// whatever `enable_system_time` happened to configure.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum TimestampMode {
+    /// Host OS clock (`Rs2Option::GlobalTimeEnabled = 0.0`, RealSense's default).
+    System,
+    /// RealSense's host-synced "global time" domain (`GlobalTimeEnabled = 1.0`).
+    Global,
+    /// Device hardware clock counter, read from the frame's metadata rather
+    /// than the SDK's own timestamp.
+    Hardware,
+}
+
+/// Read a frame's hardware clock counter out of its metadata, for feeding
+/// into `domain_timestamp`'s `Hardware` mode. `None` if this frame type
+/// doesn't expose that counter.
+pub fn frame_hardware_timestamp<F: FrameEx>(frame: &F) -> Option<f64> {
+    frame
+        .metadata(Rs2FrameMetadata::SensorTimestamp)
+        .map(|counter| counter as f64)
+}
+
+/// Resolve a timestamp in the domain selected by `mode`. `System` and
+/// `Global` both come straight from the SDK's capture timestamp, since which
+/// domain that represents is already governed by `GlobalTimeEnabled`;
+/// `Hardware` instead uses `hardware_timestamp` (see
+/// `frame_hardware_timestamp`), falling back to the capture timestamp if that
+/// counter isn't exposed by this frame type. Takes the hardware reading
+/// pre-extracted rather than the frame itself so the domain-selection logic
+/// can be exercised without a live RealSense frame.
+pub fn domain_timestamp(
+    capture_timestamp: f64,
+    hardware_timestamp: Option<f64>,
+    mode: TimestampMode,
+) -> f64 {
+    match mode {
+        TimestampMode::System | TimestampMode::Global => capture_timestamp,
+        TimestampMode::Hardware => hardware_timestamp.unwrap_or(capture_timestamp),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn domain_timestamp_system_and_global_ignore_hardware_reading() {
+        assert_eq!(
+            domain_timestamp(1.0, Some(99.0), TimestampMode::System),
+            1.0
+        );
+        assert_eq!(
+            domain_timestamp(1.0, Some(99.0), TimestampMode::Global),
+            1.0
+        );
+    }
+
+    #[test]
+    fn domain_timestamp_hardware_uses_counter_when_present() {
+        assert_eq!(
+            domain_timestamp(1.0, Some(42.0), TimestampMode::Hardware),
+            42.0
+        );
+    }
+
+    #[test]
+    fn domain_timestamp_hardware_falls_back_without_counter() {
+        assert_eq!(domain_timestamp(1.0, None, TimestampMode::Hardware), 1.0);
+    }
+
+    #[test]
+    fn deproject_recovers_the_optical_center_at_zero_offset() {
+        let intrinsics = Intrinsics {
+            width: 640,
+            height: 480,
+            ppx: 320.0,
+            ppy: 240.0,
+            fx: 600.0,
+            fy: 600.0,
+            model: DistortionModel::None,
+            coeffs: [0.0; 5],
+            depth_scale: 0.001,
+        };
+        assert_eq!(intrinsics.deproject(320.0, 240.0, 2.0), [0.0, 0.0, 2.0]);
+    }
+
+    #[test]
+    fn deproject_scales_with_depth_and_pixel_offset() {
+        let intrinsics = Intrinsics {
+            width: 640,
+            height: 480,
+            ppx: 320.0,
+            ppy: 240.0,
+            fx: 600.0,
+            fy: 600.0,
+            model: DistortionModel::None,
+            coeffs: [0.0; 5],
+            depth_scale: 0.001,
+        };
+        let point = intrinsics.deproject(920.0, 240.0, 3.0);
+        assert_eq!(point, [3.0, 0.0, 3.0]);
+    }
+}
 
 #[derive(Serialize, Deserialize, Encode, Decode, Debug, Clone)]
 pub struct CombinedFrameWire {
@@ -184,20 +379,44 @@ pub struct CombinedFrameWire {
     pub rgb_jpeg: Vec<u8>,
     /// Zstd-compressed depth buffer (u16)
     pub depth_zstd: Vec<u8>,
-    pub width:  u16,
+    pub width: u16,
     pub height: u16,
-    pub timestamp: f64,          // seconds, SYSTEM_TIME domain
+    /// depth frame's SDK capture timestamp
+    pub depth_timestamp: f64,
+    /// color frame's SDK capture timestamp, kept separate from the depth
+    /// timestamp rather than collapsed to a single clock reading
+    pub color_timestamp: f64,
+    /// `depth_timestamp` re-expressed in `timestamp_mode`'s domain, so a
+    /// consumer doesn't have to re-derive it
+    pub domain_timestamp: f64,
+    pub timestamp_mode: TimestampMode,
+    /// intrinsics of the image plane `depth_zstd` is expressed in (the color
+    /// stream's when depth is aligned to color, the depth stream's otherwise),
+    /// so a subscriber can deproject without a separate `camera/intrinsics`
+    /// round-trip if it joined mid-stream
+    pub intrinsics: Intrinsics,
 }
 
 impl CombinedFrameWire {
-    /// build from already-captured RealSense frames
-    pub fn from_frames(depth: &DepthFrame, color: &ColorFrame) -> Self {
+    /// build from already-captured RealSense frames. `jpeg_quality`/
+    /// `jpeg_subsamp` are runtime-tunable via `camera/control`, same as
+    /// `depth_zstd_level`, so an operator can still trade quality for
+    /// bandwidth on this legacy path.
+    pub fn from_frames(
+        depth: &DepthFrame,
+        color: &ColorFrame,
+        intrinsics: Intrinsics,
+        timestamp_mode: TimestampMode,
+        depth_zstd_level: i32,
+        jpeg_quality: u8,
+        jpeg_subsamp: Subsamp,
+    ) -> Self {
         // ---------- depth ----------
         let depth_ser = DepthFrameSerializable::new(depth, depth.timestamp());
         let depth_bytes = bincode::encode_to_vec(&depth_ser, bincode::config::standard()).unwrap();
         let depth_zstd = {
             let mut v = Vec::new();
-            copy_encode(&depth_bytes[..], &mut v, /*level*/ 3).unwrap();
+            copy_encode(&depth_bytes[..], &mut v, depth_zstd_level).unwrap();
             v
         };
 
@@ -213,19 +432,27 @@ impl CombinedFrameWire {
             tmp
         };
         let rgb_jpeg = compress_image::<Rgb<u8>>(
-            &ImageBuffer::from_vec(color.width() as u32,
-                                   color.height() as u32,
-                                   rgb).unwrap(),
-            /*quality*/ 75,
-            Subsamp::Sub2x2,
-        ).unwrap();
+            &ImageBuffer::from_vec(color.width() as u32, color.height() as u32, rgb).unwrap(),
+            jpeg_quality,
+            jpeg_subsamp,
+        )
+        .unwrap();
 
+        let depth_timestamp = depth.timestamp();
         Self {
             rgb_jpeg: rgb_jpeg.to_vec(),
             depth_zstd,
-            width:  color.width()  as u16,
+            width: color.width() as u16,
             height: color.height() as u16,
-            timestamp: depth.timestamp(),  // pick one clock domain
+            depth_timestamp,
+            color_timestamp: color.timestamp(),
+            domain_timestamp: domain_timestamp(
+                depth_timestamp,
+                frame_hardware_timestamp(depth),
+                timestamp_mode,
+            ),
+            timestamp_mode,
+            intrinsics,
         }
     }
 
@@ -246,14 +473,23 @@ impl CombinedFrameWire {
     }
 
     // helper to get fully-expanded data back out
-    pub fn unpack(self) -> (Vec<u8>, Vec<u16>, u16, u16, f64) {
-        let rgb_raw = turbojpeg::decompress_image::<Rgb<u8>>(&self.rgb_jpeg).unwrap().into_raw();
+    pub fn unpack(self) -> (Vec<u8>, Vec<u16>, u16, u16, f64, Intrinsics) {
+        let rgb_raw = turbojpeg::decompress_image::<Rgb<u8>>(&self.rgb_jpeg)
+            .unwrap()
+            .into_raw();
         let depth_raw = {
             let bytes = decode_all(&self.depth_zstd[..]).unwrap();
             let (d, _): (DepthFrameSerializable, _) =
                 bincode::decode_from_slice(&bytes, bincode::config::standard()).unwrap();
             d.data
         };
-        (rgb_raw, depth_raw, self.width, self.height, self.timestamp)
+        (
+            rgb_raw,
+            depth_raw,
+            self.width,
+            self.height,
+            self.domain_timestamp,
+            self.intrinsics,
+        )
     }
 }